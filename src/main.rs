@@ -15,13 +15,93 @@ You should have received a copy of the GNU Affero General Public License
 along with this program. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use log::LevelFilter;
+use rustix::event::{poll, PollFd, PollFlags};
+use rustix::time::{
+    timerfd_create, timerfd_settime, Itimerspec, Timespec, TimerfdClockId, TimerfdFlags,
+    TimerfdTimerFlags,
+};
 use serde_derive::Deserialize;
 use std::{
-    env, fs, io::Read, os::unix::net::UnixStream, path::PathBuf, process::exit, process::Command,
-    thread, time::Duration,
+    env,
+    fs,
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    process::exit,
+    process::Command,
+    process::Stdio,
+    sync::Mutex,
+    thread,
+    time::Duration,
 };
 use toml;
 
+const ACPID_SOCKET: &str = "/var/run/acpid.socket";
+
+/// Path of Hyprland's own event socket (`socket2`), which emits a
+/// newline-terminated line per compositor event, including
+/// `monitoraddedv2>>...` and `monitorremoved>>...` on hotplug. `None` when
+/// `HYPRLAND_INSTANCE_SIGNATURE` isn't set (i.e. not running under Hyprland).
+fn hyprland_event_socket_path() -> Option<PathBuf> {
+    let runtime_dir = env::var("XDG_RUNTIME_DIR").ok()?;
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+    Some(
+        PathBuf::from(runtime_dir)
+            .join("hypr")
+            .join(signature)
+            .join(".socket2.sock"),
+    )
+}
+
+/// Path of the daemon's control socket, used both to bind (`--server`) and
+/// to connect (every other CLI invocation, to forward its action instead of
+/// racing the daemon).
+fn control_socket_path() -> PathBuf {
+    let runtime_dir = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir).join("hyprdock.sock")
+}
+
+/// Forwards `command` to a running `--server` daemon over the control
+/// socket. Returns `None` when no daemon is listening, so the caller can
+/// fall back to running the action in this process.
+fn forward_to_daemon(command: &str) -> Option<String> {
+    let mut stream = UnixStream::connect(control_socket_path()).ok()?;
+    stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .ok()?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).ok()?;
+    Some(reply)
+}
+
+/// Runs `command` through the daemon if one is listening, otherwise falls
+/// back to `action` in this process.
+fn run_or_forward(command: &str, action: impl FnOnce()) {
+    match forward_to_daemon(command) {
+        Some(reply) => print!("{}", reply),
+        None => action(),
+    }
+}
+
+#[derive(Deserialize, serde_derive::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Monitor {
+    name: String,
+    #[serde(default)]
+    id: i64,
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    #[serde(default)]
+    refresh_rate: f64,
+    #[serde(flatten)]
+    extra: std::collections::HashMap<String, serde_json::Value>,
+}
+
 #[derive(Deserialize)]
 struct HyprDock {
     monitor_name: String,
@@ -39,15 +119,72 @@ struct HyprDock {
     extend_command: String,
     mirror_command: String,
     wallpaper_command: String,
+    #[serde(default)]
+    vars: std::collections::HashMap<String, String>,
+    #[serde(default = "default_log_level")]
+    log_level: String,
+    #[serde(default)]
+    log_file: Option<String>,
+    #[serde(default)]
+    syslog: bool,
+    #[serde(default)]
+    profiles: std::collections::BTreeMap<String, Profile>,
+    #[serde(skip)]
+    forced_profile: Mutex<Option<String>>,
+    /// Serializes every monitor-mutating action (lid events on the acpid
+    /// thread, commands forwarded over the control socket) so they can't
+    /// race each other inside one daemon process.
+    #[serde(skip)]
+    action_lock: Mutex<()>,
+    /// Set by `with_cached_monitors` for the duration of one action, so
+    /// `list_monitors` returns the same snapshot instead of re-running
+    /// `get_monitors_command` for every caller within that action (e.g.
+    /// `is_internal_active`, `select_profile` and `render` all want it).
+    #[serde(skip)]
+    monitor_cache: Mutex<Option<Vec<Monitor>>>,
+}
+
+/// A named dock layout: which external monitor(s) it applies to, and the
+/// commands that replace the top-level `extend_command`/`mirror_command`/
+/// `enable_external_monitor_command` when it is selected.
+#[derive(Deserialize, Debug, Clone)]
+struct Profile {
+    #[serde(default)]
+    match_monitor_name: Option<String>,
+    #[serde(default)]
+    match_monitor_count: Option<usize>,
+    /// Whether a lid-open that selects this profile should extend or mirror
+    /// onto the external monitor. Defaults to `"extend"`; set to `"mirror"`
+    /// for a projector-style profile.
+    #[serde(default = "default_profile_layout")]
+    layout: String,
+    #[serde(default)]
+    extend_command: Option<String>,
+    #[serde(default)]
+    mirror_command: Option<String>,
+    #[serde(default)]
+    enable_external_monitor_command: Option<String>,
+}
+
+fn default_profile_layout() -> String {
+    String::from("extend")
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
     if args.len() < 2 {
         print_help();
         return;
     }
 
+    let forced_profile = args
+        .iter()
+        .position(|a| a == "--profile")
+        .and_then(|pos| args.get(pos + 1).cloned().map(|name| (pos, name)));
+    if let Some((pos, _)) = forced_profile {
+        args.drain(pos..=pos + 1);
+    }
+
     let dock = parse_config(
         home::home_dir()
             .unwrap()
@@ -55,6 +192,16 @@ fn main() {
             .to_str()
             .unwrap(),
     );
+    init_logging(&dock);
+    let forced_profile = forced_profile.map(|(_, name)| name);
+    dock.set_forced_profile(forced_profile.clone());
+
+    // Encoded alongside the verb (`"EXTEND projector"`) so a forwarded
+    // command reaches the daemon with the same `--profile` the caller asked
+    // for, instead of silently applying the daemon's own default.
+    let profile_suffix = forced_profile
+        .map(|name| format!(" {}", name))
+        .unwrap_or_default();
 
     let mut iter = args.iter();
     iter.next();
@@ -65,10 +212,18 @@ fn main() {
         }
         iteration += 1;
         match iter.next().unwrap().as_str() {
-            "--internal" | "-i" => dock.internal_monitor(),
-            "--external" | "-e" => dock.external_monitor(),
-            "--extend" | "-eo" => dock.extend_monitor(),
-            "--mirror" | "-io" => dock.mirror_monitor(),
+            "--internal" | "-i" => run_or_forward(&format!("INTERNAL{}", profile_suffix), || {
+                dock.with_cached_monitors(|| dock.internal_monitor())
+            }),
+            "--external" | "-e" => run_or_forward(&format!("EXTERNAL{}", profile_suffix), || {
+                dock.with_cached_monitors(|| dock.external_monitor())
+            }),
+            "--extend" | "-eo" => run_or_forward(&format!("EXTEND{}", profile_suffix), || {
+                dock.with_cached_monitors(|| dock.extend_monitor())
+            }),
+            "--mirror" | "-io" => run_or_forward(&format!("MIRROR{}", profile_suffix), || {
+                dock.with_cached_monitors(|| dock.mirror_monitor())
+            }),
             "--server" | "-s" => dock.socket_connect(),
             "--suspend" | "-su" => dock.lock_system(),
             "--version" | "-v" => println!("0.2.1"),
@@ -95,11 +250,88 @@ fn print_help() {
             --server/-s:    daemon version
                             automatically handles actions on laptop lid close and open.
             --bar/-b:       selects a bar to start when monitor switches (used for eww)
+            --profile <name>: forces a dock profile instead of auto-matching one
             --help/-h:      shows options
             --version/-v:   shows version\n"
     );
 }
 
+fn default_log_level() -> String {
+    String::from("info")
+}
+
+/// Minimal `log::Log` backend that writes to `log_file` and/or syslog, as
+/// configured in the TOML. Falls back to stderr when neither is set, so the
+/// daemon always leaves a trace instead of failing silently.
+struct HyprDockLogger {
+    level: LevelFilter,
+    file: Option<Mutex<fs::File>>,
+    syslog: bool,
+}
+
+impl log::Log for HyprDockLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{}] {}\n", record.level(), record.args());
+
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.write_all(line.as_bytes());
+            }
+        }
+        if self.syslog {
+            // A dedicated syslog backend (e.g. the `syslog` crate) would post
+            // to the local socket here; stderr is systemd-journal's syslog
+            // shim when hyprdock runs as a unit.
+            eprint!("{}", line);
+        }
+        if self.file.is_none() && !self.syslog {
+            eprint!("{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+fn init_logging(dock: &HyprDock) {
+    let level = match dock.log_level.to_lowercase().as_str() {
+        "trace" => LevelFilter::Trace,
+        "debug" => LevelFilter::Debug,
+        "warn" => LevelFilter::Warn,
+        "error" => LevelFilter::Error,
+        "off" => LevelFilter::Off,
+        _ => LevelFilter::Info,
+    };
+    let file = dock.log_file.as_ref().and_then(|path| {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Mutex::new)
+            .map_err(|e| eprintln!("failed to open log file `{}`: {}", path, e))
+            .ok()
+    });
+
+    log::set_max_level(level);
+    let _ = log::set_boxed_logger(Box::new(HyprDockLogger {
+        level,
+        file,
+        syslog: dock.syslog,
+    }));
+}
+
 fn parse_config(path: &str) -> HyprDock {
     let contents = match fs::read_to_string(path) {
         Ok(c) => c,
@@ -111,9 +343,9 @@ fn parse_config(path: &str) -> HyprDock {
             suspend_command = 'systemctl suspend'
             lock_command = 'swaylock -c 000000'
             utility_command = 'playerctl --all-players -a pause'
-            get_monitors_command = 'hyprctl monitors'
+            get_monitors_command = 'hyprctl monitors -j'
             enable_internal_monitor_command = 'hyprctl keyword monitor {monitor_name},highrr,0x0,1'
-            disable_internal_monitor_command = 'hyprctl keyword monitor {monitor_name},diabled'
+            disable_internal_monitor_command = 'hyprctl keyword monitor {monitor_name},disabled'
             enable_external_monitor_command = 'hyprctl keyword monitor ,highrr,0x0,1'
             disable_external_monitor_command = 'hyprctl keyword monitor ,disabled'
             extend_command = 'hyprctl keyword monitor ,highrr,1920x0,1'
@@ -131,63 +363,186 @@ fn parse_config(path: &str) -> HyprDock {
 }
 
 impl HyprDock {
+    /// Expands `{monitor_name}`, `{external_name}`, `{external_width}`,
+    /// `{external_height}`, `{external_refresh_rate}` and any user-defined
+    /// `[vars]` entries in `template` before it is handed to the shell.
+    pub fn render(&self, template: &str) -> String {
+        let mut out = template.replace("{monitor_name}", &self.monitor_name);
+
+        // `list_monitors()` shells out to `get_monitors_command`; skip it for
+        // templates that don't reference the external monitor at all (e.g.
+        // `reload_bar_command`, `suspend_command`) instead of spawning
+        // `hyprctl monitors -j` on every single action.
+        let needs_external = template.contains("{external_name}")
+            || template.contains("{external_width}")
+            || template.contains("{external_height}")
+            || template.contains("{external_refresh_rate}");
+        if needs_external {
+            if let Some(external) = self
+                .list_monitors()
+                .into_iter()
+                .find(|m| m.name != self.monitor_name)
+            {
+                out = out.replace("{external_name}", &external.name);
+                out = out.replace("{external_width}", &external.width.to_string());
+                out = out.replace("{external_height}", &external.height.to_string());
+                out = out.replace(
+                    "{external_refresh_rate}",
+                    &external.refresh_rate.to_string(),
+                );
+            }
+        }
+
+        for (key, value) in &self.vars {
+            out = out.replace(&format!("{{{}}}", key), value);
+        }
+
+        out
+    }
+
     pub fn execute_command(&self, command: &str) {
+        self.run_command(&self.render(command));
+    }
+
+    /// Runs `command` verbatim, without the templating pass. Used internally
+    /// for things like `get_monitors_command`, which `render` itself depends
+    /// on to resolve `{external_name}` and friends.
+    /// Launches `command` without blocking the caller (bar/wallpaper
+    /// restarts, `suspend_command`, ... shouldn't stall lid-event handling),
+    /// but still reaps the child on a detached thread and logs its real exit
+    /// status/stderr instead of declaring success the instant it launches.
+    fn run_command(&self, command: &str) {
         let command_split: Vec<&str> = command.split(" ").collect();
         if command_split.len() == 0 {
             return;
         }
         let (first, rest) = command_split.split_first().unwrap();
-        Command::new(first)
-            .args(rest)
-            .spawn()
-            .expect("Could not parse command, please check your toml");
+        match Command::new(first).args(rest).stderr(Stdio::piped()).spawn() {
+            Ok(mut child) => {
+                log::debug!("launched `{}`", command);
+                let command = command.to_string();
+                let mut stderr = child.stderr.take();
+                thread::spawn(move || match child.wait() {
+                    Ok(status) if status.success() => {
+                        log::debug!("`{}` exited with {}", command, status)
+                    }
+                    Ok(status) => {
+                        let mut stderr_text = String::new();
+                        if let Some(stderr) = stderr.as_mut() {
+                            let _ = stderr.read_to_string(&mut stderr_text);
+                        }
+                        log::warn!("`{}` exited with {}: {}", command, status, stderr_text.trim());
+                    }
+                    Err(e) => log::error!("failed to wait on `{}`: {}", command, e),
+                });
+            }
+            Err(e) => log::error!("failed to execute `{}`: {}", command, e),
+        }
     }
 
-    pub fn execute_command_with_output(&self, command: &str) -> Vec<u8> {
+    fn run_command_with_output(&self, command: &str) -> Vec<u8> {
         let command_split: Vec<&str> = command.split(" ").collect();
         if command_split.len() == 0 {
             return Vec::new();
         }
         let (first, rest) = command_split.split_first().unwrap();
-        Command::new(first)
-            .args(rest)
-            .output()
-            .expect("Could not parse command, please check your toml")
-            .stdout
+        match Command::new(first).args(rest).output() {
+            Ok(output) => {
+                if !output.status.success() {
+                    log::warn!(
+                        "`{}` exited with {}: {}",
+                        command,
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                }
+                output.stdout
+            }
+            Err(e) => {
+                log::error!("failed to execute `{}`: {}", command, e);
+                Vec::new()
+            }
+        }
     }
 
     pub fn handle_close(&self) {
-        if self.has_external_monitor() {
-            self.external_monitor();
-            thread::sleep(Duration::from_millis(1000));
-            self.restart_hyprpaper();
-            self.restart_eww_bar();
-        } else {
-            self.stop_music();
-            self.lock_system();
-        }
+        let _guard = self.action_lock.lock().unwrap();
+        self.with_cached_monitors(|| {
+            if self.has_external_monitor() {
+                log::info!("lid closed with an external monitor attached: switching to external");
+                self.external_monitor();
+                thread::sleep(Duration::from_millis(1000));
+                self.restart_hyprpaper();
+                self.restart_eww_bar();
+            } else {
+                log::info!("lid closed with no external monitor: suspending");
+                self.stop_music();
+                self.lock_system();
+            }
+        });
     }
 
     pub fn handle_open(&self) {
-        if self.is_internal_active() {
-            return;
-        }
-        if !self.has_external_monitor() {
-            self.internal_monitor();
-            self.restart_hyprpaper();
-            self.restart_eww_bar();
-            self.fix_eww_bar();
-            return;
-        } else {
-            self.internal_monitor();
-            self.extend_monitor();
-            self.restart_hyprpaper();
-            self.restart_eww_bar();
-            self.fix_eww_bar();
+        let _guard = self.action_lock.lock().unwrap();
+        self.with_cached_monitors(|| {
+            if self.is_internal_active() {
+                return;
+            }
+            if !self.has_external_monitor() {
+                log::info!("lid opened with no external monitor: switching to internal");
+                self.internal_monitor();
+                self.restart_hyprpaper();
+                self.restart_eww_bar();
+                self.fix_eww_bar();
+                return;
+            } else {
+                self.internal_monitor();
+                self.apply_external_layout("lid opened with an external monitor attached");
+            }
+        });
+    }
+
+    /// Re-applies whichever profile now matches the live monitor list. Called
+    /// on the same cadence as `handle_open`'s external branch, but triggered
+    /// by a Hyprland `monitoradded`/`monitorremoved` event instead of a lid
+    /// toggle, so plugging in a dock while the lid is already open also
+    /// selects the right layout.
+    pub fn handle_monitor_change(&self) {
+        let _guard = self.action_lock.lock().unwrap();
+        self.with_cached_monitors(|| {
+            if !self.has_external_monitor() {
+                return;
+            }
+            self.apply_external_layout("monitor hotplug detected");
+        });
+    }
+
+    /// Shared by `handle_open` and `handle_monitor_change`: picks the
+    /// matching profile (if any) and extends or mirrors onto the external
+    /// monitor accordingly, then restarts the bar/wallpaper for the new
+    /// layout. `context` only changes the log message's lead-in.
+    fn apply_external_layout(&self, context: &str) {
+        match self.select_profile() {
+            Some((name, profile)) if profile.layout == "mirror" => {
+                log::info!("{}: mirroring via profile `{}`", context, name);
+                self.mirror_monitor();
+            }
+            Some((name, _)) => {
+                log::info!("{}: extending via profile `{}`", context, name);
+                self.extend_monitor();
+            }
+            None => {
+                log::info!("{}: extending", context);
+                self.extend_monitor();
+            }
         }
+        self.restart_hyprpaper();
+        self.restart_eww_bar();
+        self.fix_eww_bar();
     }
 
     pub fn handle_event(&self, event: &str) {
+        log::debug!("received acpi event: {}", event.trim_end());
         match event {
             "button/lid LID close\n" => self.handle_close(),
             "button/lid LID open\n" => self.handle_open(),
@@ -195,15 +550,191 @@ impl HyprDock {
         }
     }
 
+    /// Dispatches one line from Hyprland's event socket. Only hotplug events
+    /// matter here; everything else (workspace switches, window events, ...)
+    /// is ignored.
+    pub fn handle_hyprland_event(&self, event: &str) {
+        let event = event.trim_end();
+        log::debug!("received hyprland event: {}", event);
+        if event.starts_with("monitoraddedv2") || event.starts_with("monitorremoved") {
+            self.handle_monitor_change();
+        }
+    }
+
     pub fn socket_connect(&self) {
-        let mut sock =
-            UnixStream::connect("/var/run/acpid.socket").expect("failed to connect to socket");
+        thread::scope(|scope| {
+            scope.spawn(|| self.run_control_server());
+            scope.spawn(|| self.run_hyprland_event_loop());
+            self.run_acpid_loop();
+        });
+    }
+
+    fn run_acpid_loop(&self) {
+        loop {
+            match UnixStream::connect(ACPID_SOCKET) {
+                Ok(sock) => match self.run_event_loop(sock, |event| self.handle_event(event)) {
+                    Ok(()) => eprintln!("acpid connection closed, reconnecting..."),
+                    Err(e) => eprintln!("acpid event loop error: {}, reconnecting...", e),
+                },
+                Err(e) => eprintln!("failed to connect to {}: {}, retrying...", ACPID_SOCKET, e),
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    /// Mirrors `run_acpid_loop`, but for Hyprland's own event socket, so a
+    /// monitor plugged in while the lid stays open still triggers profile
+    /// re-selection (see `handle_monitor_change`). A no-op when
+    /// `HYPRLAND_INSTANCE_SIGNATURE` isn't set, e.g. outside Hyprland.
+    fn run_hyprland_event_loop(&self) {
+        let Some(path) = hyprland_event_socket_path() else {
+            log::warn!("HYPRLAND_INSTANCE_SIGNATURE not set: hotplug detection disabled");
+            return;
+        };
+        loop {
+            match UnixStream::connect(&path) {
+                Ok(sock) => match self.run_event_loop(sock, |event| self.handle_hyprland_event(event)) {
+                    Ok(()) => eprintln!("hyprland event socket closed, reconnecting..."),
+                    Err(e) => eprintln!("hyprland event loop error: {}, reconnecting...", e),
+                },
+                Err(e) => eprintln!("failed to connect to {}: {}, retrying...", path.display(), e),
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    /// Listens on the control socket so CLI invocations can forward their
+    /// action to this daemon instead of racing it (see `forward_to_daemon`).
+    fn run_control_server(&self) {
+        let path = control_socket_path();
+        let _ = fs::remove_file(&path);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("failed to bind control socket {}: {}", path.display(), e);
+                return;
+            }
+        };
+        for conn in listener.incoming() {
+            match conn {
+                Ok(stream) => self.handle_control_connection(stream),
+                Err(e) => eprintln!("control socket accept error: {}", e),
+            }
+        }
+    }
+
+    fn handle_control_connection(&self, mut stream: UnixStream) {
+        let mut buf = [0u8; 256];
+        let n = match stream.read(&mut buf) {
+            Ok(n) => n,
+            Err(_) => return,
+        };
+        let line = std::str::from_utf8(&buf[..n]).unwrap_or("").trim().to_string();
+        let mut parts = line.splitn(2, ' ');
+        let verb = parts.next().unwrap_or("");
+        let profile = parts.next().map(|s| s.trim().to_string());
+
+        let reply = {
+            // Holds action_lock for the whole dispatch (forced_profile set
+            // through execution) so a forwarded command can't interleave
+            // with a concurrent lid event in handle_close/open, and so its
+            // `--profile` can't be clobbered by another connection's. The
+            // prior forced profile (e.g. one set via `--server --profile
+            // <name>` at daemon startup) is restored afterward rather than
+            // cleared, so a plain forwarded command doesn't wipe it.
+            let _guard = self.action_lock.lock().unwrap();
+            let prior_profile = self.forced_profile.lock().unwrap().clone();
+            if profile.is_some() {
+                self.set_forced_profile(profile);
+            }
+            let reply = self.with_cached_monitors(|| match verb {
+                "INTERNAL" => {
+                    self.internal_monitor();
+                    "OK".to_string()
+                }
+                "EXTERNAL" => {
+                    self.external_monitor();
+                    "OK".to_string()
+                }
+                "EXTEND" => {
+                    self.extend_monitor();
+                    "OK".to_string()
+                }
+                "MIRROR" => {
+                    self.mirror_monitor();
+                    "OK".to_string()
+                }
+                "STATUS" => {
+                    serde_json::to_string(&self.list_monitors()).unwrap_or_else(|_| "[]".to_string())
+                }
+                other => format!("ERR unknown command {}", other),
+            });
+            self.set_forced_profile(prior_profile);
+            reply
+        };
+        let _ = stream.write_all(reply.as_bytes());
+    }
+
+    /// Polls `sock` alongside a debounce timerfd and dispatches each
+    /// complete newline-terminated line to `on_line`, but not immediately:
+    /// each line rearms a one-shot `DEBOUNCE` timer and only the last line
+    /// pending when it fires is dispatched. This keeps a rapid run of lid
+    /// toggles (or hotplug flaps) from each running to completion serially
+    /// under `action_lock` -- only the settled final state does. Shared by
+    /// the acpid loop and the Hyprland event-socket loop, so both get the
+    /// same accumulate-and-split framing and the same clean-EOF-means-
+    /// reconnect handling instead of panicking.
+    fn run_event_loop(
+        &self,
+        mut sock: UnixStream,
+        mut on_line: impl FnMut(&str),
+    ) -> std::io::Result<()> {
+        const DEBOUNCE: Duration = Duration::from_millis(150);
+
+        let timer = timerfd_create(TimerfdClockId::Monotonic, TimerfdFlags::empty())
+            .map_err(std::io::Error::from)?;
+        let mut acc: Vec<u8> = Vec::new();
+        let mut buf = [0u8; 1024];
+        let mut pending: Option<String> = None;
+
         loop {
-            let mut buf = [0; 1024];
-            let n = sock.read(&mut buf).expect("failed to read from socket");
-            let data = std::str::from_utf8(&buf[..n]).unwrap().to_string();
+            let mut fds = [
+                PollFd::new(&sock, PollFlags::IN),
+                PollFd::new(&timer, PollFlags::IN),
+            ];
+            poll(&mut fds, -1).map_err(std::io::Error::from)?;
+
+            if fds[1].revents().contains(PollFlags::IN) {
+                let mut expirations = [0u8; 8];
+                let _ = rustix::io::read(&timer, &mut expirations);
+                if let Some(event) = pending.take() {
+                    on_line(&event);
+                }
+            }
 
-            self.handle_event(data.as_str());
+            if fds[0].revents().contains(PollFlags::IN) {
+                let n = sock.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(());
+                }
+                acc.extend_from_slice(&buf[..n]);
+
+                while let Some(pos) = acc.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = acc.drain(..=pos).collect();
+                    if let Ok(event) = std::str::from_utf8(&line) {
+                        pending = Some(event.to_string());
+                        let deadline = Itimerspec {
+                            it_interval: Timespec { tv_sec: 0, tv_nsec: 0 },
+                            it_value: Timespec {
+                                tv_sec: DEBOUNCE.as_secs() as _,
+                                tv_nsec: DEBOUNCE.subsec_nanos() as _,
+                            },
+                        };
+                        timerfd_settime(&timer, TimerfdTimerFlags::empty(), &deadline)
+                            .map_err(std::io::Error::from)?;
+                    }
+                }
+            }
         }
     }
 
@@ -220,14 +751,74 @@ impl HyprDock {
         if !self.is_internal_active() {
             self.restart_internal();
         }
-        self.execute_command(self.extend_command.as_str());
+        let command = match self.select_profile() {
+            Some((name, profile)) => {
+                log::info!("using profile `{}` for extend layout", name);
+                profile.extend_command.unwrap_or_else(|| self.extend_command.clone())
+            }
+            None => self.extend_command.clone(),
+        };
+        self.execute_command(&command);
     }
 
     pub fn mirror_monitor(&self) {
         if !self.is_internal_active() {
             self.restart_internal();
         }
-        self.execute_command(self.mirror_command.as_str());
+        let command = match self.select_profile() {
+            Some((name, profile)) => {
+                log::info!("using profile `{}` for mirror layout", name);
+                profile.mirror_command.unwrap_or_else(|| self.mirror_command.clone())
+            }
+            None => self.mirror_command.clone(),
+        };
+        self.execute_command(&command);
+    }
+
+    /// Forces `select_profile` to use the named profile instead of
+    /// auto-matching one against the current monitor list (the CLI's
+    /// `--profile <name>` flag).
+    pub fn set_forced_profile(&self, name: Option<String>) {
+        *self.forced_profile.lock().unwrap() = name;
+    }
+
+    /// Picks the dock profile that applies right now: the forced profile if
+    /// `--profile` was passed (directly or forwarded over the control
+    /// socket), otherwise the first profile (in profile-name order) whose
+    /// `match_monitor_name`/`match_monitor_count` matches the live monitor
+    /// list. Re-evaluated on every lid event and every CLI/control-socket
+    /// command; there is no dedicated hardware hotplug source (e.g. udev) in
+    /// this daemon, so a monitor that appears without a lid toggle or a
+    /// manual command in between won't trigger a re-selection on its own.
+    fn select_profile(&self) -> Option<(String, Profile)> {
+        if let Some(name) = self.forced_profile.lock().unwrap().clone() {
+            let profile = self.profiles.get(&name).cloned();
+            if profile.is_none() {
+                log::warn!("--profile `{}` does not match any configured profile", name);
+            }
+            return profile.map(|profile| (name, profile));
+        }
+
+        let monitors = self.list_monitors();
+        self.profiles
+            .iter()
+            .find(|(_, profile)| self.profile_matches(profile, &monitors))
+            .map(|(name, profile)| (name.clone(), profile.clone()))
+    }
+
+    fn profile_matches(&self, profile: &Profile, monitors: &[Monitor]) -> bool {
+        if let Some(name) = &profile.match_monitor_name {
+            if !monitors.iter().any(|m| &m.name == name) {
+                return false;
+            }
+        }
+        if let Some(count) = profile.match_monitor_count {
+            let external_count = monitors.iter().filter(|m| m.name != self.monitor_name).count();
+            if external_count != count {
+                return false;
+            }
+        }
+        true
     }
 
     pub fn internal_monitor(&self) {
@@ -253,7 +844,16 @@ impl HyprDock {
         }
         let needs_restart = !self.is_internal_active();
         self.execute_command(self.disable_internal_monitor_command.as_str());
-        self.execute_command(self.enable_external_monitor_command.as_str());
+        let command = match self.select_profile() {
+            Some((name, profile)) => {
+                log::info!("using profile `{}` for external monitor layout", name);
+                profile
+                    .enable_external_monitor_command
+                    .unwrap_or_else(|| self.enable_external_monitor_command.clone())
+            }
+            None => self.enable_external_monitor_command.clone(),
+        };
+        self.execute_command(&command);
         if needs_restart {
             self.restart_eww_bar();
             self.restart_hyprpaper();
@@ -273,23 +873,42 @@ impl HyprDock {
         self.execute_command(self.reload_bar_command.as_str());
     }
 
-    pub fn is_internal_active(&self) -> bool {
-        let output =
-            String::from_utf8(self.execute_command_with_output(self.get_monitors_command.as_str()))
-                .unwrap();
-        if output.contains(self.monitor_name.as_str()) {
-            return true;
+    pub fn list_monitors(&self) -> Vec<Monitor> {
+        if let Some(cached) = self.monitor_cache.lock().unwrap().clone() {
+            return cached;
         }
-        false
+        self.fetch_monitors()
+    }
+
+    fn fetch_monitors(&self) -> Vec<Monitor> {
+        let output = self.run_command_with_output(self.get_monitors_command.as_str());
+        serde_json::from_slice(&output).unwrap_or_else(|_| {
+            eprintln!("Unable to parse `{}` output as JSON", self.get_monitors_command);
+            Vec::new()
+        })
+    }
+
+    /// Runs `action`, primed with a single `get_monitors_command` snapshot
+    /// that every `list_monitors()` call inside `action` reuses, instead of
+    /// each of `is_internal_active`/`select_profile`/`render` shelling out
+    /// to `hyprctl monitors -j` on its own. Cleared again once `action`
+    /// returns so unrelated later calls still see a fresh list.
+    fn with_cached_monitors<T>(&self, action: impl FnOnce() -> T) -> T {
+        *self.monitor_cache.lock().unwrap() = Some(self.fetch_monitors());
+        let result = action();
+        *self.monitor_cache.lock().unwrap() = None;
+        result
+    }
+
+    pub fn is_internal_active(&self) -> bool {
+        self.list_monitors()
+            .iter()
+            .any(|m| m.name == self.monitor_name && !m.disabled)
     }
 
     pub fn has_external_monitor(&self) -> bool {
-        let output =
-            String::from_utf8(self.execute_command_with_output(self.get_monitors_command.as_str()))
-                .unwrap();
-        if output.contains("ID 1") {
-            return true;
-        }
-        false
+        self.list_monitors()
+            .iter()
+            .any(|m| m.name != self.monitor_name)
     }
 }